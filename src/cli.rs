@@ -1,43 +1,241 @@
-use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
-use failure::Error;
+use crate::shell::ShellMode;
+use anyhow::{anyhow, ensure, Error};
+use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, Shell, SubCommand};
+use std::io;
+use std::process;
 use std::str::FromStr;
 
+const BIN_NAME: &str = "rayt";
+
+pub struct ConfigPath(String);
+
+impl ConfigPath {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone)]
+pub struct OutputPath(String);
+
+impl OutputPath {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+pub struct ImagePath(String);
+
+impl ImagePath {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
 pub enum CliCommand {
     RENDER {
         width: u64,
-        output_path: String,
+        output_path: OutputPath,
         num_of_rays: u64,
         num_of_threads: usize,
+        asset_paths: Vec<ImagePath>,
+        preview: Option<Preview>,
+    },
+    GENERATE {
+        scene: crate::scenes::Scene,
     },
-    GENERATE,
+}
+
+/// How often, and where, to flush a progressive preview snapshot while a
+/// render is in progress. See the `preview` module.
+pub struct Preview {
+    pub interval_secs: u64,
+    pub path: OutputPath,
 }
 
 pub struct CliConfig {
-    pub command: CliCommand,
-    pub config_path: String,
+    command: CliCommand,
+    config_path: ConfigPath,
+    shell_mode: ShellMode,
 }
 
-#[derive(Debug, Fail)]
-enum CliParsingError {
-    #[fail(display = "invalid value <{}> for arg <{}>", value, arg)]
-    InvalidValue { arg: String, value: String },
+impl CliConfig {
+    pub fn command(&self) -> &CliCommand {
+        &self.command
+    }
+
+    pub fn config_path(&self) -> &ConfigPath {
+        &self.config_path
+    }
+
+    pub fn shell_mode(&self) -> ShellMode {
+        self.shell_mode
+    }
+}
+
+/// Best-effort `--json`/`--quiet` detection straight from the process's
+/// raw args, independent of clap. `get_cli_config` needs this for the
+/// failures clap itself raises (a missing required flag, an invalid
+/// value, `--help`) or that happen before a parsed `ArgMatches` exists to
+/// read the real shell mode from — those still need to report through the
+/// right `Shell` rather than a hardcoded `Normal` one.
+pub fn shell_mode_from_raw_args() -> ShellMode {
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--json") {
+        ShellMode::Json
+    } else if args.iter().any(|arg| arg == "--quiet" || arg == "-q") {
+        ShellMode::Quiet
+    } else {
+        ShellMode::Normal
+    }
 }
 
 pub fn get_cli_config() -> Result<CliConfig, Error> {
-    let matches = App::new("Ray tracer")
+    // `get_matches_safe` (rather than `get_matches`, which prints straight
+    // to stderr and exits on its own) so a bad flag is reported through
+    // `shell_mode_from_raw_args`'s shell like every other parse failure,
+    // not always as plain text regardless of `--json`.
+    let matches = match app().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(e) => {
+            // `--help`/`--version` aren't errors; clap just uses the Err
+            // path to short-circuit the rest of parsing. Keep their
+            // existing plain-text-to-stdout-then-exit-0 behavior.
+            if e.kind == clap::ErrorKind::HelpDisplayed || e.kind == clap::ErrorKind::VersionDisplayed {
+                print!("{}", e.message);
+                process::exit(0);
+            }
+            return Err(anyhow!(e.message));
+        }
+    };
+
+    if let Some(subcommand) = matches.subcommand_matches("completions") {
+        let raw_shell = subcommand.value_of("shell").unwrap();
+        let shell = raw_shell
+            .parse::<Shell>()
+            .map_err(|_| anyhow!("invalid value <{}> for arg <shell>", raw_shell))?;
+
+        app().gen_completions_to(BIN_NAME, shell, &mut io::stdout());
+        process::exit(0);
+    }
+
+    ensure!(
+        matches.value_of("config").is_some(),
+        "Missing required argument --config <path to image config yaml>",
+    );
+    let config_path = ConfigPath(String::from(matches.value_of("config").unwrap()));
+    ensure!(
+        config_path.as_str().ends_with(".yaml"),
+        "Config path <{}> must end in .yaml",
+        config_path.as_str(),
+    );
+
+    let shell_mode = if matches.is_present("json") {
+        ShellMode::Json
+    } else if matches.is_present("quiet") {
+        ShellMode::Quiet
+    } else {
+        ShellMode::Normal
+    };
+
+    if let Some(subcommand) = matches.subcommand_matches("render") {
+        let width = parse::<u64>(subcommand, "width")?;
+        let output_path = OutputPath(String::from(subcommand.value_of("output_path").unwrap()));
+        let num_of_rays = parse::<u64>(subcommand, "rays")?;
+        let num_of_threads = parse::<usize>(subcommand, "threads")?;
+        let asset_paths = subcommand
+            .values_of("assets")
+            .unwrap_or_default()
+            .map(|path| ImagePath(String::from(path)))
+            .collect();
+
+        ensure!(
+            output_path.as_str().ends_with(".ppm"),
+            "Output path <{}> must end in .ppm",
+            output_path.as_str(),
+        );
+
+        let preview = match subcommand.value_of("preview_interval") {
+            Some(_) => {
+                let interval_secs = parse::<u64>(subcommand, "preview_interval")?;
+                let path = OutputPath(String::from(
+                    subcommand
+                        .value_of("preview_path")
+                        .unwrap_or("preview.ppm"),
+                ));
+
+                ensure!(
+                    path.as_str().ends_with(".ppm"),
+                    "Preview path <{}> must end in .ppm",
+                    path.as_str(),
+                );
+
+                Some(Preview { interval_secs, path })
+            }
+            None => None,
+        };
+
+        return Ok(CliConfig {
+            command: CliCommand::RENDER {
+                width,
+                output_path,
+                num_of_rays,
+                num_of_threads,
+                asset_paths,
+                preview,
+            },
+            config_path,
+            shell_mode,
+        });
+    }
+    if let Some(subcommand) = matches.subcommand_matches("generate") {
+        let raw_scene = subcommand.value_of("scene").unwrap();
+        let scene = crate::scenes::Scene::from_str(raw_scene)
+            .map_err(|_| anyhow!("invalid value <{}> for arg <scene>", raw_scene))?;
+
+        return Ok(CliConfig {
+            command: CliCommand::GENERATE { scene },
+            config_path,
+            shell_mode,
+        });
+    }
+
+    // Clap should have errored before we get here
+    panic!("Unable to parse CLI args")
+}
+
+fn app<'a, 'b>() -> App<'a, 'b> {
+    App::new("Ray tracer")
         .setting(AppSettings::SubcommandRequiredElseHelp)
         .setting(AppSettings::VersionlessSubcommands)
         .global_setting(AppSettings::ColoredHelp)
         .global_setting(AppSettings::DeriveDisplayOrder)
         .version(crate_version!())
         .arg(
+            // Not marked `required` here: the `completions` subcommand
+            // doesn't need it, so presence is enforced in `get_cli_config`
+            // for the subcommands that do.
             Arg::with_name("config")
                 .short("c")
                 .long("config")
                 .takes_value(true)
-                .required(true)
+                .global(true)
                 .help("path to image config yaml"),
         )
+        .arg(
+            Arg::with_name("quiet")
+                .short("q")
+                .long("quiet")
+                .global(true)
+                .help("suppress step/progress output; print errors only"),
+        )
+        .arg(
+            Arg::with_name("json")
+                .long("json")
+                .global(true)
+                .help("emit newline-delimited JSON events on stdout instead of human text"),
+        )
+        .group(ArgGroup::with_name("output_mode").args(&["quiet", "json"]))
         .subcommands(vec![
             SubCommand::with_name("render")
                 .about("renders an image")
@@ -75,58 +273,51 @@ pub fn get_cli_config() -> Result<CliConfig, Error> {
                         .required(true)
                         .default_value("4")
                         .help("the number of threads to create for the renderer"),
+                )
+                .arg(
+                    Arg::with_name("assets")
+                        .short("a")
+                        .long("asset")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("path to a texture image referenced by the scene; may be repeated"),
+                )
+                .arg(
+                    Arg::with_name("preview_interval")
+                        .long("preview-interval")
+                        .takes_value(true)
+                        .help("seconds between progressive preview snapshots"),
+                )
+                .arg(
+                    Arg::with_name("preview_path")
+                        .long("preview-path")
+                        .takes_value(true)
+                        .requires("preview_interval")
+                        .help("where to write preview snapshots [default: preview.ppm]"),
+                ),
+            SubCommand::with_name("generate")
+                .about("generate a random image config yaml")
+                .arg(
+                    Arg::with_name("scene")
+                        .takes_value(true)
+                        .required(true)
+                        .help("the scene to generate"),
+                ),
+            SubCommand::with_name("completions")
+                .about("generate a shell completion script")
+                .arg(
+                    Arg::with_name("shell")
+                        .takes_value(true)
+                        .required(true)
+                        .possible_values(&["bash", "zsh", "fish", "powershell", "elvish"])
+                        .help("the shell to generate a completion script for"),
                 ),
-            SubCommand::with_name("generate").about("generate a random image config yaml"),
         ])
-        .get_matches();
-
-    let config_path = String::from(matches.value_of("config").unwrap());
-    ensure!(
-        config_path.ends_with(".yaml"),
-        "Config path <{}> must end in .yaml",
-        config_path,
-    );
-
-    if let Some(subcommand) = matches.subcommand_matches("render") {
-        let width = parse::<u64>(subcommand, "width")?;
-        let output_path = String::from(subcommand.value_of("output_path").unwrap());
-        let num_of_rays = parse::<u64>(subcommand, "rays")?;
-        let num_of_threads = parse::<usize>(subcommand, "threads")?;
-
-        ensure!(
-            output_path.ends_with(".ppm"),
-            "Output path <{}> must end in .ppm",
-            output_path,
-        );
-
-        return Ok(CliConfig {
-            command: CliCommand::RENDER {
-                width,
-                output_path,
-                num_of_rays,
-                num_of_threads,
-            },
-            config_path,
-        });
-    }
-    if matches.subcommand_matches("generate").is_some() {
-        return Ok(CliConfig {
-            command: CliCommand::GENERATE,
-            config_path,
-        });
-    }
-
-    // Clap should have errored before we get here
-    panic!("Unable to parse CLI args")
 }
 
-fn parse<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<T, CliParsingError> {
+fn parse<T: FromStr>(matches: &ArgMatches, arg: &str) -> Result<T, Error> {
     let raw = matches.value_of(arg).unwrap();
-    match raw.parse::<T>() {
-        Ok(parsed) => Ok(parsed),
-        Err(_) => Err(CliParsingError::InvalidValue {
-            arg: String::from(arg),
-            value: String::from(raw),
-        }),
-    }
+    raw.parse::<T>()
+        .map_err(|_| anyhow!("invalid value <{}> for arg <{}>", raw, arg))
 }
@@ -0,0 +1,254 @@
+//! Scene configuration: the on-disk YAML shape (`ConfigSave`) and the
+//! resolved, render-ready `Config` built from it once assets and plugins
+//! are available.
+//!
+//! `sphere`/`lambertian` are the only object/material type names rayt
+//! understands natively; any other type name must be claimed by one of
+//! the scene's `plugins`, so third parties can add primitives and
+//! materials without recompiling rayt. Which is which is decided once,
+//! here in `into_config`, which rejects a type name no plugin claims; the
+//! renderer and `pdf::scatter` still look up the owning plugin by type
+//! name each time they dispatch to it.
+
+use crate::data::assets::Assets;
+use crate::plugin::PluginManager;
+use crate::world::{Material, Object, World};
+use anyhow::{anyhow, Error};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Deserialize)]
+struct ObjectSave {
+    id: String,
+    #[serde(rename = "type")]
+    type_name: String,
+    material: String,
+    #[serde(default)]
+    center: [f64; 3],
+    #[serde(default)]
+    radius: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct MaterialSave {
+    #[serde(rename = "type")]
+    type_name: String,
+    #[serde(default)]
+    albedo: [f64; 3],
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct CameraSave {
+    look_from: [f64; 3],
+    look_at: [f64; 3],
+    vfov: f64,
+    #[serde(default = "default_aspect_ratio")]
+    aspect_ratio: f64,
+}
+
+fn default_aspect_ratio() -> f64 {
+    16.0 / 9.0
+}
+
+/// The on-disk scene shape, written by `generate` and read by `render`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ConfigSave {
+    #[serde(default)]
+    plugins: Vec<PathBuf>,
+    objects: Vec<ObjectSave>,
+    materials: HashMap<String, MaterialSave>,
+    camera: CameraSave,
+}
+
+impl ConfigSave {
+    pub fn plugins(&self) -> &[PathBuf] {
+        &self.plugins
+    }
+
+    /// Check that every object's `material` field names an entry in
+    /// `materials`, since `Config::material` looks it up by that name at
+    /// render time and would otherwise panic on an unresolvable scene.
+    /// Object/material *type* names aren't checked here: confirming one is
+    /// plugin-owned needs the plugin handshake, which hasn't happened yet
+    /// at this point in `run_render` (see `into_config`, which runs after
+    /// `PluginManager::load`).
+    ///
+    /// `assets` isn't consulted: rayt's materials are solid `albedo`
+    /// colors, not image textures, so there's no texture-path field on
+    /// `ObjectSave`/`MaterialSave` yet to cross-check against it.
+    pub fn validate(&self, _assets: &Assets) -> Result<(), Error> {
+        for object in &self.objects {
+            if !self.materials.contains_key(&object.material) {
+                return Err(anyhow!(
+                    "object <{}> references unknown material <{}>",
+                    object.id,
+                    object.material
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Resolve every object/material's type name against rayt's native
+    /// types and `plugins`, building the renderable `World` and erroring
+    /// out if a type name is neither.
+    pub fn into_config(self, width: u32, num_of_rays: u64, assets: Assets, plugins: PluginManager) -> Result<Config, Error> {
+        let materials = self
+            .materials
+            .into_iter()
+            .map(|(name, save)| {
+                let material = match save.type_name.as_str() {
+                    "lambertian" => Material::Lambertian { albedo: save.albedo },
+                    plugin_type if plugins.resolve_material(plugin_type).is_some() => Material::Plugin {
+                        type_name: plugin_type.to_string(),
+                        id: name.clone(),
+                    },
+                    other => return Err(anyhow!("no plugin claims material type <{}>", other)),
+                };
+                Ok((name, material))
+            })
+            .collect::<Result<HashMap<_, _>, Error>>()?;
+
+        let objects = self
+            .objects
+            .into_iter()
+            .map(|save| match save.type_name.as_str() {
+                "sphere" => Ok(Object::Sphere {
+                    center: save.center,
+                    radius: save.radius,
+                    material: save.material,
+                }),
+                plugin_type if plugins.resolve_object(plugin_type).is_some() => Ok(Object::Plugin {
+                    id: save.id,
+                    plugin_type: plugin_type.to_string(),
+                    material: save.material,
+                }),
+                other => Err(anyhow!("no plugin claims object type <{}>", other)),
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let world = World::build(objects, &plugins)?;
+        let height = ((f64::from(width) / self.camera.aspect_ratio).round() as u32).max(1);
+        let camera = Camera::new(self.camera.look_from, self.camera.look_at, self.camera.vfov, self.camera.aspect_ratio);
+
+        Ok(Config {
+            world,
+            materials,
+            camera,
+            plugins,
+            assets,
+            width,
+            height,
+            num_of_rays,
+        })
+    }
+}
+
+/// A simple pinhole camera: `ray` maps normalized image coordinates
+/// `(u, v)` in `[0, 1]` to a world-space ray through that point on the
+/// image plane.
+pub struct Camera {
+    origin: [f64; 3],
+    lower_left: [f64; 3],
+    horizontal: [f64; 3],
+    vertical: [f64; 3],
+}
+
+impl Camera {
+    fn new(look_from: [f64; 3], look_at: [f64; 3], vfov_degrees: f64, aspect_ratio: f64) -> Camera {
+        let theta = vfov_degrees.to_radians();
+        let viewport_height = 2.0 * (theta / 2.0).tan();
+        let viewport_width = aspect_ratio * viewport_height;
+
+        let w = normalize(sub(look_from, look_at));
+        let up = [0.0, 1.0, 0.0];
+        let u = normalize(cross(up, w));
+        let v = cross(w, u);
+
+        let horizontal = scale(u, viewport_width);
+        let vertical = scale(v, viewport_height);
+        let lower_left = sub(sub(sub(look_from, scale(horizontal, 0.5)), scale(vertical, 0.5)), w);
+
+        Camera {
+            origin: look_from,
+            lower_left,
+            horizontal,
+            vertical,
+        }
+    }
+
+    pub fn ray(&self, u: f64, v: f64) -> ([f64; 3], [f64; 3]) {
+        let dir = sub(
+            add(add(self.lower_left, scale(self.horizontal, u)), scale(self.vertical, v)),
+            self.origin,
+        );
+        (self.origin, dir)
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(a: [f64; 3], t: f64) -> [f64; 3] {
+    [a[0] * t, a[1] * t, a[2] * t]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let len = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+    [a[0] / len, a[1] / len, a[2] / len]
+}
+
+/// The fully resolved, render-ready form of a scene: world geometry and
+/// materials with every type name already resolved to native or plugin,
+/// the camera, and everything the renderer needs to dispatch to a plugin
+/// mid-render.
+pub struct Config {
+    world: World,
+    materials: HashMap<String, Material>,
+    camera: Camera,
+    plugins: PluginManager,
+    #[allow(dead_code)]
+    assets: Assets,
+    width: u32,
+    height: u32,
+    num_of_rays: u64,
+}
+
+impl Config {
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn num_of_rays(&self) -> u64 {
+        self.num_of_rays
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn camera(&self) -> &Camera {
+        &self.camera
+    }
+
+    pub fn plugins(&self) -> &PluginManager {
+        &self.plugins
+    }
+
+    pub fn material(&self, name: &str) -> &Material {
+        &self.materials[name]
+    }
+}
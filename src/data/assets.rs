@@ -0,0 +1,141 @@
+//! Decoded texture images available to the scene, keyed by asset path.
+//!
+//! Textures used to be decoded one at a time in a plain loop, which stalled
+//! startup on scenes with many images and aborted the whole load on the
+//! first bad file. `Assets::new` instead fans every path out as a rayon
+//! task, with a per-asset progress bar in a shared `MultiProgress` so users
+//! can see which files are still decoding, and collects every decode
+//! failure it hits into one `AssetLoadError` instead of stopping at the
+//! first one.
+
+use crate::cli::ImagePath;
+use crate::shell::ShellMode;
+use image::DynamicImage;
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::thread;
+
+const ASSET_PROGRESS_BAR_STYLE: &str = "{prefix:.bold.dim} [{bar:30.cyan/blue}] {msg}";
+
+/// The texture images referenced by a scene, decoded up front and looked
+/// up by the path the scene config used to name them.
+pub struct Assets {
+    images: HashMap<String, DynamicImage>,
+}
+
+impl Assets {
+    /// `mode` comes from the run's `Shell`: per-asset progress bars only
+    /// make sense in `Normal` mode, so `Quiet`/`Json` skip building them
+    /// entirely rather than rendering bars `Shell` has no way to suppress.
+    pub fn new(asset_paths: &[ImagePath], mode: ShellMode) -> Result<Assets, AssetLoadError> {
+        let mut draw_thread = None;
+        let bars: Option<Vec<ProgressBar>> = match mode {
+            ShellMode::Normal if !asset_paths.is_empty() => {
+                let multi_progress = MultiProgress::new();
+                let bars: Vec<ProgressBar> = asset_paths
+                    .iter()
+                    .map(|path| {
+                        let bar = multi_progress.add(ProgressBar::new(1));
+                        bar.set_style(
+                            ProgressStyle::default_bar()
+                                .template(ASSET_PROGRESS_BAR_STYLE)
+                                .progress_chars("##-"),
+                        );
+                        bar.set_prefix(path.as_str());
+                        bar.set_message("decoding");
+                        bar
+                    })
+                    .collect();
+
+                // `MultiProgress::join` blocks until every bar above
+                // finishes, so it gets its own thread while the rayon pool
+                // does the decoding. With at least one bar added above,
+                // this always returns once `finish_with_message` is called
+                // on each of them.
+                draw_thread = Some(thread::spawn(move || {
+                    let _ = multi_progress.join();
+                }));
+
+                Some(bars)
+            }
+            _ => None,
+        };
+
+        let mut results: Vec<(String, Result<DynamicImage, AssetLoadErrorEntry>)> = asset_paths
+            .par_iter()
+            .enumerate()
+            .map(|(i, path)| {
+                let decoded = image::open(path.as_str()).map_err(|e| AssetLoadErrorEntry {
+                    path: path.as_str().to_string(),
+                    message: e.to_string(),
+                });
+
+                if let Some(bars) = &bars {
+                    match &decoded {
+                        Ok(_) => bars[i].finish_with_message("done"),
+                        Err(e) => bars[i].finish_with_message(&e.message),
+                    }
+                }
+
+                (path.as_str().to_string(), decoded)
+            })
+            .collect();
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        if let Some(draw_thread) = draw_thread {
+            let _ = draw_thread.join();
+        }
+
+        let mut images = HashMap::with_capacity(results.len());
+        let mut errors = Vec::new();
+        for (path, decoded) in results {
+            match decoded {
+                Ok(image) => {
+                    images.insert(path, image);
+                }
+                Err(e) => errors.push(e),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AssetLoadError { errors });
+        }
+
+        Ok(Assets { images })
+    }
+
+    pub fn get(&self, path: &str) -> Option<&DynamicImage> {
+        self.images.get(path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+}
+
+#[derive(Debug)]
+struct AssetLoadErrorEntry {
+    path: String,
+    message: String,
+}
+
+/// Every decode failure hit while loading assets, reported together
+/// instead of aborting on the first one.
+#[derive(Debug)]
+pub struct AssetLoadError {
+    errors: Vec<AssetLoadErrorEntry>,
+}
+
+impl fmt::Display for AssetLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "failed to load {} asset(s):", self.errors.len())?;
+        for error in &self.errors {
+            writeln!(f, "  {}: {}", error.path, error.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssetLoadError {}
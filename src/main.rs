@@ -13,33 +13,48 @@ mod float;
 mod io;
 mod onb;
 mod pdf;
+mod plugin;
+mod preview;
 mod renderer;
 mod scenes;
+mod shell;
 mod world;
 
-use crate::cli::{get_cli_config, CliCommand, ConfigPath, ImagePath, OutputPath};
-use crate::config::Config;
+use crate::cli::{get_cli_config, CliCommand, ConfigPath, ImagePath, OutputPath, Preview};
 use crate::data::assets::Assets;
 use crate::io::{load_config, save_config};
+use crate::plugin::PluginManager;
+use crate::preview::{PreviewBuffer, PreviewWriter};
 use crate::renderer::render;
 use crate::scenes::{build_scene_config, Scene};
-use console::style;
-use indicatif::{FormattedDuration, ProgressBar, ProgressStyle};
+use crate::shell::Shell;
 use std::process;
-use std::time::Instant;
-
-const PROGRESS_BAR_STYLE: &str = "[{elapsed_precise}] [{bar:60.cyan/blue}] {percent}% ({eta})";
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 fn main() {
-    if let Err(e) = run() {
-        eprintln!("{} {}", style("error:").red(), e);
+    // A full `CliConfig` (and the exact shell mode it resolved) only
+    // exists once `get_cli_config` returns `Ok`, so any failure out of it —
+    // our own checks or a clap parse error — is reported through the mode
+    // `shell_mode_from_raw_args` can still recover straight from argv.
+    // Everything past that point runs through the shell `run` built from
+    // the user's actual `--json`/`--quiet` choice.
+    let cli_config = match get_cli_config() {
+        Ok(cli_config) => cli_config,
+        Err(e) => {
+            Shell::new(cli::shell_mode_from_raw_args()).error(&e);
+            process::exit(1);
+        }
+    };
+
+    let mut shell = Shell::new(cli_config.shell_mode());
+    if let Err(e) = run(&mut shell, &cli_config) {
+        shell.error(&e);
         process::exit(1);
     }
 }
 
-fn run() -> Result<(), anyhow::Error> {
-    let cli_config = get_cli_config()?;
-
+fn run(shell: &mut Shell, cli_config: &cli::CliConfig) -> Result<(), anyhow::Error> {
     match cli_config.command() {
         CliCommand::RENDER {
             width,
@@ -47,18 +62,21 @@ fn run() -> Result<(), anyhow::Error> {
             num_of_rays,
             num_of_threads,
             asset_paths,
+            preview,
         } => {
             run_render(
-                &cli_config.config_path(),
+                shell,
+                cli_config.config_path(),
                 *width,
                 &output_path,
                 *num_of_rays,
                 *num_of_threads,
                 asset_paths,
+                preview,
             )?;
         }
         CliCommand::GENERATE { scene } => {
-            run_generate(&scene, &cli_config.config_path())?;
+            run_generate(shell, scene, cli_config.config_path())?;
         }
     };
 
@@ -66,12 +84,14 @@ fn run() -> Result<(), anyhow::Error> {
 }
 
 fn run_render(
+    shell: &mut Shell,
     config_path: &ConfigPath,
     width: u32,
     output_path: &OutputPath,
     num_of_rays: u64,
     num_of_threads: usize,
     asset_paths: &[ImagePath],
+    preview: &Option<Preview>,
 ) -> Result<(), anyhow::Error> {
     rayon::ThreadPoolBuilder::new()
         .num_threads(num_of_threads)
@@ -79,91 +99,89 @@ fn run_render(
 
     let started = Instant::now();
 
-    let mut step_logger = StepLogger::new(7);
+    shell.begin_steps(8);
 
-    step_logger.log("Loading image yaml");
+    shell.step("Loading image yaml");
     let config_save = load_config(config_path)?;
 
-    step_logger.log("Loading assets");
-    let assets = Assets::new(asset_paths)?;
+    // Decoding is parallelized across the rayon pool (see `Assets::new`).
+    // Validation runs as its own step once decoding has finished; it only
+    // checks that each object's material name resolves and does not read
+    // the decoded assets (see `ConfigSave::validate`).
+    shell.step("Loading assets");
+    let assets = Assets::new(asset_paths, shell.mode())?;
 
-    step_logger.log("Validating assets");
+    shell.step("Validating scene references");
     config_save.validate(&assets)?;
 
-    step_logger.log("Creating config (constructing BVH)");
-    let config = config_save.into_config(width, num_of_rays, assets);
+    shell.step("Starting plugins");
+    let plugins = PluginManager::load(config_save.plugins())?;
+
+    shell.step("Creating config (resolving world geometry)");
+    let config = config_save.into_config(width, num_of_rays, assets, plugins)?;
+
+    shell.step("Rendering");
+    let progress_bar = shell.progress_bar(u64::from(config.height() * config.width()));
+
+    let preview_buffer = preview
+        .is_some()
+        .then(|| Arc::new(PreviewBuffer::new(config.width(), config.height())));
+    let preview_writer = match (&preview_buffer, preview) {
+        (Some(buffer), Some(preview)) => Some(PreviewWriter::spawn(
+            Arc::clone(buffer),
+            Duration::from_secs(preview.interval_secs),
+            preview.path.clone(),
+        )),
+        _ => None,
+    };
 
-    step_logger.log("Rendering");
-    let progress_bar = progress_bar(&config);
-    let render_output = render(&config, &progress_bar);
+    if let (Some(buffer), Some(preview)) = (&preview_buffer, preview) {
+        let ctrlc_buffer = Arc::clone(buffer);
+        let ctrlc_path = preview.path.clone();
+        ctrlc::set_handler(move || {
+            preview::write_final_snapshot(&ctrlc_buffer, &ctrlc_path);
+            process::exit(130);
+        })?;
+    }
+
+    let render_output = render(&config, progress_bar.as_ref(), preview_buffer.as_deref());
+
+    if let (Some(writer), Some(buffer), Some(preview)) = (preview_writer, &preview_buffer, preview) {
+        writer.finish(buffer, &preview.path);
+    }
 
     if render_output.failed_rays > 0 {
-        step_logger.log(&format!(
+        shell.step(&format!(
             "Checking for errors: found {} rays with errors",
             render_output.failed_rays
         ));
     } else {
-        step_logger.log("Checking for errors: no errors")
+        shell.step("Checking for errors: no errors")
     }
 
-    step_logger.log("Printing image");
+    shell.step("Printing image");
     io::write_image(render_output.image, output_path)?;
+    shell.finish();
 
-    println!("Done in {}", FormattedDuration(started.elapsed()));
+    shell.render_stats(
+        render_output.failed_rays,
+        config.width(),
+        config.height(),
+        output_path.as_str(),
+        started.elapsed(),
+    );
 
     Ok(())
 }
 
-fn run_generate(scene: &Scene, config_path: &ConfigPath) -> Result<(), anyhow::Error> {
-    let mut step_logger = StepLogger::new(2);
+fn run_generate(shell: &mut Shell, scene: &Scene, config_path: &ConfigPath) -> Result<(), anyhow::Error> {
+    shell.begin_steps(2);
 
-    step_logger.log("Generating scene");
+    shell.step("Generating scene");
     let config_save = build_scene_config(scene)?;
 
-    step_logger.log("Writing image yaml");
+    shell.step("Writing image yaml");
     save_config(config_path, config_save)?;
+    shell.finish();
     Ok(())
 }
-
-fn progress_bar(config: &Config) -> ProgressBar {
-    let progress_style = ProgressStyle::default_bar()
-        .template(PROGRESS_BAR_STYLE)
-        .progress_chars("##-");
-    let bar_size = u64::from(config.height() * config.width());
-    let progress_bar = ProgressBar::new(bar_size);
-    progress_bar.set_style(progress_style);
-    progress_bar.tick();
-    progress_bar.set_draw_delta(bar_size / 1000);
-
-    progress_bar
-}
-
-struct StepLogger {
-    step: u8,
-    num_of_steps: u8,
-}
-
-impl StepLogger {
-    fn new(num_of_steps: u8) -> StepLogger {
-        StepLogger {
-            step: 1,
-            num_of_steps,
-        }
-    }
-
-    fn log(&mut self, msg: &str) {
-        assert!(self.step <= self.num_of_steps);
-
-        println!(
-            "{}{}{}{}{} {}...",
-            style("[").bold().dim(),
-            style(self.step.to_string()).bold().dim(),
-            style("/").bold().dim(),
-            style(self.num_of_steps.to_string()).bold().dim(),
-            style("]").bold().dim(),
-            msg,
-        );
-
-        self.step += 1
-    }
-}
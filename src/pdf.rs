@@ -0,0 +1,53 @@
+//! Material response: how a ray scatters off whatever it hit.
+//!
+//! `lambertian` is the only material rayt implements natively; every other
+//! material type name belongs to whichever plugin claimed it at
+//! `ConfigSave::into_config` time (see `world::Material::Plugin`), so
+//! `scatter` just forwards to `Plugin::scatter` for those and never needs
+//! to know what the plugin actually does with the hit.
+//!
+//! `renderer::trace` doesn't recurse: shading is single-bounce, so
+//! `Scatter` only carries `attenuation`. A scattered direction isn't
+//! resolved here until the renderer actually traces a second bounce.
+
+use crate::plugin::{PluginManager, PluginHitRecord, RayQuery};
+use crate::world::{HitRecord, Material};
+use anyhow::Error;
+
+/// What a material does with a ray that hit it: how much of the incoming
+/// light it keeps.
+pub struct Scatter {
+    pub attenuation: [f64; 3],
+}
+
+/// Resolve `material`'s response to a ray arriving from direction `ray_dir`
+/// that hit it at `hit`, dispatching to whichever plugin claimed
+/// `material`'s type name when it isn't one of rayt's native materials.
+pub fn scatter(material: &Material, ray_dir: [f64; 3], hit: &HitRecord, plugins: &PluginManager) -> Result<Scatter, Error> {
+    match material {
+        Material::Lambertian { albedo } => Ok(Scatter { attenuation: *albedo }),
+        Material::Plugin { type_name, id } => {
+            let plugin = plugins
+                .resolve_material(type_name)
+                .expect("plugin ownership checked at config load time");
+
+            let ray = RayQuery {
+                origin: hit.point,
+                dir: ray_dir,
+                t_min: 0.0001,
+                t_max: f64::INFINITY,
+            };
+            let plugin_hit = PluginHitRecord {
+                t: hit.t,
+                point: hit.point,
+                normal: hit.normal,
+                material_id: id.clone(),
+                u: hit.u,
+                v: hit.v,
+            };
+
+            let response = plugin.scatter(id, &ray, &plugin_hit)?;
+            Ok(Scatter { attenuation: response.attenuation })
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Subprocess plugin protocol for externally supplied primitives and
+//! materials.
+//!
+//! A scene's `plugins` list (see `config::ConfigSave::plugins`, populated by
+//! `load_config`) names executables that speak a small JSON-RPC dialect over
+//! their stdin/stdout. At startup `run_render` builds a `PluginManager` from
+//! that list: each plugin is spawned with piped stdio and handshakes with a
+//! `describe` call to learn which object/material type names it owns.
+//! During world construction and shading, any config node whose type name
+//! is claimed by a plugin is dispatched to that plugin's process instead of
+//! to `world`/`pdf`: `hit` for ray intersection, `bounding_box` so the
+//! renderer can cull rays that miss the object entirely before paying for
+//! `hit`, and `scatter` for material response.
+//!
+//! Each plugin is spawned once, but a request/response round trip per ray
+//! would still be far too slow, so queries are batched: callers collect the rays for a whole tile and issue one framed
+//! `hit` request covering all of them, and `describe`/`bounding_box`
+//! responses (which don't vary per ray) are cached for the lifetime of the
+//! handle.
+
+use anyhow::{anyhow, Context};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::Mutex;
+
+/// A single ray-plugin query, serialized as the `params` of a `hit` request.
+#[derive(Debug, Clone, Serialize)]
+pub struct RayQuery {
+    pub origin: [f64; 3],
+    pub dir: [f64; 3],
+    pub t_min: f64,
+    pub t_max: f64,
+}
+
+/// The hit record a plugin returns for a `RayQuery` that intersected its
+/// geometry. Also re-serialized as the `hit` param of a `scatter` request,
+/// so the plugin sees the same shape it returned from `hit`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PluginHitRecord {
+    pub t: f64,
+    pub point: [f64; 3],
+    pub normal: [f64; 3],
+    pub material_id: String,
+    pub u: f64,
+    pub v: f64,
+}
+
+/// An axis-aligned bounding box, as returned by `bounding_box`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct PluginBoundingBox {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+/// The material response returned by `scatter`. Plugins may also return a
+/// `scattered_dir`, but rayt's renderer doesn't recurse past the first
+/// bounce yet, so it isn't deserialized here.
+#[derive(Debug, Clone, Deserialize)]
+pub struct PluginScatter {
+    pub attenuation: [f64; 3],
+}
+
+#[derive(Debug, Deserialize)]
+struct DescribeResponse {
+    objects: Vec<String>,
+    materials: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct RpcRequest<'a, P: Serialize> {
+    id: u64,
+    method: &'a str,
+    params: P,
+}
+
+#[derive(Deserialize)]
+struct RpcResponse<R> {
+    #[allow(dead_code)]
+    id: u64,
+    result: Option<R>,
+    error: Option<String>,
+}
+
+/// A single running plugin process plus the type names it has claimed, with
+/// caches for the per-process-lifetime `describe`/`bounding_box` responses.
+pub struct Plugin {
+    path: PathBuf,
+    objects: Vec<String>,
+    materials: Vec<String>,
+    bounding_box_cache: Mutex<HashMap<String, PluginBoundingBox>>,
+    channel: Mutex<PluginChannel>,
+}
+
+struct PluginChannel {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+    next_id: u64,
+}
+
+impl PluginChannel {
+    fn call<P: Serialize, R: for<'de> Deserialize<'de>>(
+        &mut self,
+        method: &str,
+        params: P,
+    ) -> Result<R, anyhow::Error> {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        let request = RpcRequest { id, method, params };
+        let mut line = serde_json::to_string(&request)?;
+        line.push('\n');
+        self.stdin
+            .write_all(line.as_bytes())
+            .context("writing plugin request")?;
+        self.stdin.flush()?;
+
+        let mut response_line = String::new();
+        let bytes_read = self
+            .stdout
+            .read_line(&mut response_line)
+            .context("reading plugin response")?;
+        if bytes_read == 0 {
+            return Err(anyhow!("plugin process closed its stdout"));
+        }
+
+        let response: RpcResponse<R> = serde_json::from_str(&response_line)
+            .with_context(|| format!("invalid plugin response: {}", response_line.trim()))?;
+        match (response.result, response.error) {
+            (Some(result), _) => Ok(result),
+            (None, Some(error)) => Err(anyhow!("plugin error: {}", error)),
+            (None, None) => Err(anyhow!("plugin response had neither result nor error")),
+        }
+    }
+}
+
+impl Plugin {
+    fn spawn(path: &Path) -> Result<Plugin, anyhow::Error> {
+        let mut child = Command::new(path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("spawning plugin <{}>", path.display()))?;
+
+        let stdin = child.stdin.take().expect("piped stdin");
+        let stdout = BufReader::new(child.stdout.take().expect("piped stdout"));
+
+        let mut channel = PluginChannel {
+            child,
+            stdin,
+            stdout,
+            next_id: 0,
+        };
+
+        let describe: DescribeResponse = channel
+            .call("describe", ())
+            .with_context(|| format!("handshake with plugin <{}>", path.display()))?;
+
+        Ok(Plugin {
+            path: path.to_path_buf(),
+            objects: describe.objects,
+            materials: describe.materials,
+            bounding_box_cache: Mutex::new(HashMap::new()),
+            channel: Mutex::new(channel),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn owns_object(&self, type_name: &str) -> bool {
+        self.objects.iter().any(|name| name == type_name)
+    }
+
+    pub fn owns_material(&self, type_name: &str) -> bool {
+        self.materials.iter().any(|name| name == type_name)
+    }
+
+    /// Dispatch a batch of ray queries for one tile in a single framed
+    /// request, returning one optional hit record per query.
+    pub fn hit_batch(
+        &self,
+        object_id: &str,
+        queries: &[RayQuery],
+    ) -> Result<Vec<Option<PluginHitRecord>>, anyhow::Error> {
+        #[derive(Serialize)]
+        struct Params<'a> {
+            object_id: &'a str,
+            queries: &'a [RayQuery],
+        }
+
+        let mut channel = self.channel.lock().unwrap();
+        channel.call(
+            "hit",
+            Params {
+                object_id,
+                queries,
+            },
+        )
+    }
+
+    pub fn bounding_box(&self, object_id: &str) -> Result<PluginBoundingBox, anyhow::Error> {
+        if let Some(cached) = self.bounding_box_cache.lock().unwrap().get(object_id) {
+            return Ok(*cached);
+        }
+
+        #[derive(Serialize)]
+        struct Params<'a> {
+            object_id: &'a str,
+        }
+
+        let bbox: PluginBoundingBox = self
+            .channel
+            .lock()
+            .unwrap()
+            .call("bounding_box", Params { object_id })?;
+        self.bounding_box_cache
+            .lock()
+            .unwrap()
+            .insert(object_id.to_string(), bbox);
+        Ok(bbox)
+    }
+
+    pub fn scatter(
+        &self,
+        material_id: &str,
+        ray: &RayQuery,
+        hit: &PluginHitRecord,
+    ) -> Result<PluginScatter, anyhow::Error> {
+        #[derive(Serialize)]
+        struct Params<'a> {
+            material_id: &'a str,
+            ray: &'a RayQuery,
+            hit: &'a PluginHitRecord,
+        }
+
+        self.channel.lock().unwrap().call(
+            "scatter",
+            Params {
+                material_id,
+                ray,
+                hit,
+            },
+        )
+    }
+}
+
+/// Owns every plugin process spawned for a render and resolves object/
+/// material type names to the plugin that claimed them.
+pub struct PluginManager {
+    plugins: Vec<Plugin>,
+}
+
+impl PluginManager {
+    pub fn load(plugin_paths: &[PathBuf]) -> Result<PluginManager, anyhow::Error> {
+        let plugins = plugin_paths
+            .iter()
+            .map(|path| Plugin::spawn(path))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(PluginManager { plugins })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+
+    pub fn resolve_object(&self, type_name: &str) -> Option<&Plugin> {
+        self.plugins.iter().find(|plugin| plugin.owns_object(type_name))
+    }
+
+    pub fn resolve_material(&self, type_name: &str) -> Option<&Plugin> {
+        self.plugins
+            .iter()
+            .find(|plugin| plugin.owns_material(type_name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rpc_request_serializes_with_id_method_and_params() {
+        let request = RpcRequest {
+            id: 7,
+            method: "hit",
+            params: RayQuery {
+                origin: [0.0, 0.0, 0.0],
+                dir: [0.0, 0.0, 1.0],
+                t_min: 0.001,
+                t_max: f64::INFINITY,
+            },
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&serde_json::to_string(&request).unwrap()).unwrap();
+        assert_eq!(value["id"], 7);
+        assert_eq!(value["method"], "hit");
+        assert_eq!(value["params"]["origin"], serde_json::json!([0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn rpc_response_deserializes_a_result_with_a_null_error() {
+        let response: RpcResponse<PluginBoundingBox> = serde_json::from_str(
+            r#"{"id":1,"result":{"min":[-1.0,-1.0,-1.0],"max":[1.0,1.0,1.0]},"error":null}"#,
+        )
+        .unwrap();
+
+        let bbox = response.result.unwrap();
+        assert_eq!(bbox.min, [-1.0, -1.0, -1.0]);
+        assert_eq!(bbox.max, [1.0, 1.0, 1.0]);
+        assert!(response.error.is_none());
+    }
+
+    #[test]
+    fn rpc_response_deserializes_an_error() {
+        let response: RpcResponse<PluginBoundingBox> =
+            serde_json::from_str(r#"{"id":2,"result":null,"error":"no such object"}"#).unwrap();
+
+        assert!(response.result.is_none());
+        assert_eq!(response.error.as_deref(), Some("no such object"));
+    }
+
+    #[test]
+    fn plugin_hit_record_round_trips_through_json() {
+        let hit = PluginHitRecord {
+            t: 1.5,
+            point: [0.0, 1.0, 2.0],
+            normal: [0.0, 1.0, 0.0],
+            material_id: "glass".to_string(),
+            u: 0.25,
+            v: 0.75,
+        };
+
+        let json = serde_json::to_string(&hit).unwrap();
+        let decoded: PluginHitRecord = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(decoded.t, hit.t);
+        assert_eq!(decoded.point, hit.point);
+        assert_eq!(decoded.normal, hit.normal);
+        assert_eq!(decoded.material_id, hit.material_id);
+        assert_eq!(decoded.u, hit.u);
+        assert_eq!(decoded.v, hit.v);
+    }
+
+    #[test]
+    fn plugin_scatter_ignores_unknown_fields_like_scattered_dir() {
+        let scatter: PluginScatter =
+            serde_json::from_str(r#"{"attenuation":[0.8,0.8,0.8],"scattered_dir":[0.0,1.0,0.0]}"#).unwrap();
+        assert_eq!(scatter.attenuation, [0.8, 0.8, 0.8]);
+    }
+
+    #[test]
+    fn describe_response_deserializes_object_and_material_lists() {
+        let describe: DescribeResponse =
+            serde_json::from_str(r#"{"objects":["sdf_blob"],"materials":["glow"]}"#).unwrap();
+        assert_eq!(describe.objects, vec!["sdf_blob".to_string()]);
+        assert_eq!(describe.materials, vec!["glow".to_string()]);
+    }
+}
@@ -0,0 +1,210 @@
+//! Background writer for progressive preview snapshots.
+//!
+//! Multi-minute renders produce no output until `render` returns. When
+//! `--preview-interval` is set, `run_render` shares a `PreviewBuffer` with
+//! the renderer's rayon workers: each worker folds every ray sample into
+//! the buffer as it goes, and the buffer keeps a per-pixel running mean. A
+//! background thread wakes up every interval, takes a snapshot of the
+//! buffer, tone-maps it, and writes it to `--preview-path`, so users can
+//! watch convergence without stopping the job. The buffer tracks a version
+//! counter so the writer can skip snapshots when nothing has changed. A
+//! final snapshot is written by `PreviewWriter::finish` when the render
+//! completes, or by `write_final_snapshot` from the Ctrl-C handler
+//! `run_render` installs when the job is interrupted.
+
+use crate::cli::OutputPath;
+use crate::io;
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Samples are accumulated as fixed-point integers so each channel can be
+/// folded in with a single lock-free `fetch_add`, rather than a
+/// read-modify-write under a lock.
+const FIXED_POINT_SCALE: f64 = 65536.0;
+
+/// A single accumulated pixel: a fixed-point running sum of samples plus
+/// how many samples have been folded into it so far, each updated with its
+/// own atomic so workers touching different pixels never contend.
+#[derive(Default)]
+struct AtomicPreviewPixel {
+    sum: [AtomicU64; 3],
+    samples: AtomicU32,
+}
+
+/// The shared buffer the renderer's workers publish into and the
+/// background writer snapshots from. `accumulate` never takes a lock: each
+/// pixel has its own atomics, so rayon workers updating different pixels
+/// never serialize behind one another.
+pub struct PreviewBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<AtomicPreviewPixel>,
+    version: AtomicU64,
+}
+
+impl PreviewBuffer {
+    pub fn new(width: u32, height: u32) -> PreviewBuffer {
+        let num_pixels = (width * height) as usize;
+        let pixels = (0..num_pixels).map(|_| AtomicPreviewPixel::default()).collect();
+
+        PreviewBuffer {
+            width,
+            height,
+            pixels,
+            version: AtomicU64::new(0),
+        }
+    }
+
+    /// Fold one more sample into `index`'s running mean. Called by a
+    /// rayon worker once per completed ray; lock-free, so it never blocks
+    /// on another worker's update to a different pixel.
+    pub fn accumulate(&self, index: usize, color: [f64; 3]) {
+        let pixel = &self.pixels[index];
+        for (channel, value) in pixel.sum.iter().zip(color.iter()) {
+            let fixed_point = (value.max(0.0) * FIXED_POINT_SCALE) as u64;
+            channel.fetch_add(fixed_point, Ordering::Relaxed);
+        }
+        pixel.samples.fetch_add(1, Ordering::Relaxed);
+
+        self.version.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn version(&self) -> u64 {
+        self.version.load(Ordering::Relaxed)
+    }
+
+    fn tone_mapped_rgb8(&self) -> Vec<u8> {
+        let mut rgb8 = Vec::with_capacity(self.pixels.len() * 3);
+        for pixel in &self.pixels {
+            let samples = (pixel.samples.load(Ordering::Relaxed) as f64).max(1.0);
+            for channel in &pixel.sum {
+                let sum = channel.load(Ordering::Relaxed) as f64 / FIXED_POINT_SCALE;
+                let mean = sum / samples;
+                let gamma_corrected = mean.max(0.0).sqrt();
+                rgb8.push((gamma_corrected.min(1.0) * 255.0) as u8);
+            }
+        }
+        rgb8
+    }
+}
+
+/// How long the background loop sleeps between checks of the stop flag.
+/// `--preview-interval` can be multi-minute, so sleeping it in one shot
+/// would make `finish` block for up to that long after the render is
+/// already done; polling in short increments keeps shutdown snappy.
+const STOP_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Drives the background snapshot loop for the lifetime of a render.
+pub struct PreviewWriter {
+    handle: Option<JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+}
+
+impl PreviewWriter {
+    pub fn spawn(buffer: Arc<PreviewBuffer>, interval: Duration, path: OutputPath) -> PreviewWriter {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = Arc::clone(&stop);
+
+        let handle = thread::spawn(move || {
+            let mut last_version = 0;
+            while !sleep_or_stop(&worker_stop, interval) {
+                let version = buffer.version();
+                if version == last_version {
+                    continue;
+                }
+                last_version = version;
+                write_snapshot(&buffer, &path);
+            }
+        });
+
+        PreviewWriter {
+            handle: Some(handle),
+            stop,
+        }
+    }
+
+    /// Stop the background loop and write one last snapshot covering
+    /// whatever samples have accumulated so far.
+    pub fn finish(mut self, buffer: &PreviewBuffer, path: &OutputPath) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+        write_snapshot(buffer, path);
+    }
+}
+
+/// Write one snapshot of `buffer`'s current state to `path`, ignoring (and
+/// logging) write errors rather than propagating them — a failed preview
+/// write should never abort the render itself.
+pub fn write_final_snapshot(buffer: &PreviewBuffer, path: &OutputPath) {
+    write_snapshot(buffer, path)
+}
+
+fn write_snapshot(buffer: &PreviewBuffer, path: &OutputPath) {
+    let rgb8 = buffer.tone_mapped_rgb8();
+    if let Err(e) = io::write_rgb8_image(buffer.width, buffer.height, &rgb8, path) {
+        eprintln!("warning: failed to write preview snapshot: {}", e);
+    }
+}
+
+/// Sleep for `interval`, but in `STOP_POLL_INTERVAL`-sized chunks so a
+/// concurrent `stop` store is noticed well before `interval` elapses.
+/// Returns whether `stop` was set.
+fn sleep_or_stop(stop: &AtomicBool, interval: Duration) -> bool {
+    let mut remaining = interval;
+    while remaining > Duration::ZERO {
+        let chunk = remaining.min(STOP_POLL_INTERVAL);
+        thread::sleep(chunk);
+        if stop.load(Ordering::Relaxed) {
+            return true;
+        }
+        remaining -= chunk;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accumulate_folds_samples_into_a_running_mean() {
+        let buffer = PreviewBuffer::new(1, 1);
+        buffer.accumulate(0, [1.0, 0.0, 0.0]);
+        buffer.accumulate(0, [0.0, 0.0, 0.0]);
+
+        // Two samples averaging to 0.5 on the red channel, gamma-corrected
+        // by the same sqrt curve `tone_mapped_rgb8` applies.
+        let expected_red = ((0.5f64).sqrt() * 255.0) as u8;
+        assert_eq!(buffer.tone_mapped_rgb8(), vec![expected_red, 0, 0]);
+    }
+
+    #[test]
+    fn tone_mapped_rgb8_is_black_before_any_samples() {
+        let buffer = PreviewBuffer::new(2, 1);
+        assert_eq!(buffer.tone_mapped_rgb8(), vec![0, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn version_only_advances_on_accumulate() {
+        let buffer = PreviewBuffer::new(1, 1);
+        assert_eq!(buffer.version(), 0);
+        buffer.accumulate(0, [0.2, 0.2, 0.2]);
+        assert_eq!(buffer.version(), 1);
+    }
+
+    #[test]
+    fn sleep_or_stop_returns_immediately_once_stop_is_set() {
+        let stop = AtomicBool::new(true);
+        assert!(sleep_or_stop(&stop, Duration::from_secs(3600)));
+    }
+
+    #[test]
+    fn sleep_or_stop_runs_out_the_interval_when_never_stopped() {
+        let stop = AtomicBool::new(false);
+        assert!(!sleep_or_stop(&stop, Duration::from_millis(1)));
+    }
+}
@@ -0,0 +1,235 @@
+//! Turns a resolved `Config` into pixels.
+//!
+//! Each image row is rendered as one tile: for every plugin-owned object,
+//! the row's rays are first culled against that object's cached bounding
+//! box (`World::bounding_box_hit`), and only the survivors are batched into
+//! a single `Plugin::hit_batch` call (a subprocess round trip per ray would
+//! be far too slow). Each ray is then walked against that batch
+//! plus rayt's native spheres to find the closest hit and shade it.
+
+use crate::config::Config;
+use crate::pdf;
+use crate::plugin::{PluginHitRecord, RayQuery};
+use crate::preview::PreviewBuffer;
+use crate::world::{HitRecord, Object};
+use indicatif::ProgressBar;
+use rand::Rng;
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const T_MIN: f64 = 0.001;
+
+/// The rendered image (tightly packed RGB8 rows) plus how many rays hit an
+/// unrecoverable error (a plugin call failing) rather than a geometric
+/// miss.
+pub struct RenderOutput {
+    pub image: Vec<u8>,
+    pub failed_rays: u64,
+}
+
+pub fn render(config: &Config, progress_bar: Option<&ProgressBar>, preview_buffer: Option<&PreviewBuffer>) -> RenderOutput {
+    let failed_rays = AtomicU64::new(0);
+
+    let rows: Vec<Vec<u8>> = (0..config.height())
+        .into_par_iter()
+        .map(|y| render_row(config, y, &failed_rays, preview_buffer, progress_bar))
+        .collect();
+
+    RenderOutput {
+        image: rows.into_iter().flatten().collect(),
+        failed_rays: failed_rays.load(Ordering::Relaxed),
+    }
+}
+
+/// Render one scanline, batching every plugin-owned object's hit test for
+/// the whole row into one `Plugin::hit_batch` call.
+fn render_row(
+    config: &Config,
+    y: u32,
+    failed_rays: &AtomicU64,
+    preview_buffer: Option<&PreviewBuffer>,
+    progress_bar: Option<&ProgressBar>,
+) -> Vec<u8> {
+    let width = config.width();
+    let samples_per_pixel = config.num_of_rays();
+
+    let rays: Vec<(u32, [f64; 3], [f64; 3])> = (0..width)
+        .flat_map(|x| (0..samples_per_pixel).map(move |_| pixel_ray(config, x, y)))
+        .collect();
+
+    let plugin_hits = batch_plugin_hits(config, &rays, failed_rays);
+
+    let mut sums = vec![[0.0f64; 3]; width as usize];
+    for (ray_index, &(x, origin, dir)) in rays.iter().enumerate() {
+        let color = trace(config, origin, dir, &plugin_hits, ray_index, failed_rays);
+        sums[x as usize] = add(sums[x as usize], color);
+
+        if let Some(buffer) = preview_buffer {
+            buffer.accumulate((y as usize) * (width as usize) + x as usize, color);
+        }
+    }
+
+    if let Some(bar) = progress_bar {
+        bar.inc(u64::from(width));
+    }
+
+    sums.into_iter()
+        .flat_map(|sum| to_rgb8(scale(sum, 1.0 / samples_per_pixel as f64)))
+        .collect()
+}
+
+fn pixel_ray(config: &Config, x: u32, y: u32) -> (u32, [f64; 3], [f64; 3]) {
+    let mut rng = rand::thread_rng();
+    let u = (x as f64 + rng.gen::<f64>()) / (config.width().max(2) - 1) as f64;
+    let v = (y as f64 + rng.gen::<f64>()) / (config.height().max(2) - 1) as f64;
+    let (origin, dir) = config.camera().ray(u, 1.0 - v);
+    (x, origin, dir)
+}
+
+/// Issue one `hit_batch` call per plugin-owned object, restricted to the
+/// rays whose bounding box it actually overlaps, and index the results by
+/// ray position within the row (rays that missed the box, or weren't sent,
+/// are `None`).
+fn batch_plugin_hits(
+    config: &Config,
+    rays: &[(u32, [f64; 3], [f64; 3])],
+    failed_rays: &AtomicU64,
+) -> HashMap<usize, Vec<Option<PluginHitRecord>>> {
+    config
+        .world()
+        .objects()
+        .iter()
+        .enumerate()
+        .filter_map(|(index, object)| match object {
+            Object::Plugin { id, plugin_type, .. } => Some((index, id, plugin_type)),
+            Object::Sphere { .. } => None,
+        })
+        .map(|(index, id, plugin_type)| {
+            let plugin = config
+                .plugins()
+                .resolve_object(plugin_type)
+                .expect("plugin ownership checked at config load time");
+
+            let candidate_rays: Vec<usize> = rays
+                .iter()
+                .enumerate()
+                .filter_map(|(ray_index, &(_, origin, dir))| {
+                    config
+                        .world()
+                        .bounding_box_hit(index, origin, dir, T_MIN, f64::INFINITY)
+                        .then_some(ray_index)
+                })
+                .collect();
+
+            let queries: Vec<RayQuery> = candidate_rays
+                .iter()
+                .map(|&ray_index| {
+                    let (_, origin, dir) = rays[ray_index];
+                    RayQuery {
+                        origin,
+                        dir,
+                        t_min: T_MIN,
+                        t_max: f64::INFINITY,
+                    }
+                })
+                .collect();
+
+            let mut hits = vec![None; rays.len()];
+            if !queries.is_empty() {
+                match plugin.hit_batch(id, &queries) {
+                    Ok(batch) if batch.len() == queries.len() => {
+                        for (ray_index, hit) in candidate_rays.into_iter().zip(batch) {
+                            hits[ray_index] = hit;
+                        }
+                    }
+                    // Either an outright failure or a plugin returning the
+                    // wrong number of hits, which is just as unusable:
+                    // there's no sound way to line its response up with
+                    // `queries`.
+                    _ => {
+                        failed_rays.fetch_add(queries.len() as u64, Ordering::Relaxed);
+                    }
+                };
+            }
+
+            (index, hits)
+        })
+        .collect()
+}
+
+/// Find the closest hit for one ray among the native spheres and this
+/// row's precomputed plugin hits, then shade it.
+fn trace(
+    config: &Config,
+    origin: [f64; 3],
+    dir: [f64; 3],
+    plugin_hits: &HashMap<usize, Vec<Option<PluginHitRecord>>>,
+    ray_index: usize,
+    failed_rays: &AtomicU64,
+) -> [f64; 3] {
+    let mut record = config.world().hit_spheres(origin, dir, T_MIN, f64::INFINITY);
+    let mut closest_t = record.as_ref().map_or(f64::INFINITY, |hit| hit.t);
+
+    for (&object_index, hits) in plugin_hits {
+        if let Some(plugin_hit) = &hits[ray_index] {
+            if plugin_hit.t > T_MIN && plugin_hit.t < closest_t {
+                closest_t = plugin_hit.t;
+                record = Some(HitRecord {
+                    t: plugin_hit.t,
+                    point: plugin_hit.point,
+                    normal: plugin_hit.normal,
+                    material: config.world().objects()[object_index].material().to_string(),
+                    u: plugin_hit.u,
+                    v: plugin_hit.v,
+                });
+            }
+        }
+    }
+
+    match record {
+        None => background(dir),
+        Some(hit) => match pdf::scatter(config.material(&hit.material), dir, &hit, config.plugins()) {
+            Ok(scatter) => scale(scatter.attenuation, facing_light(hit.normal).max(0.1)),
+            Err(_) => {
+                failed_rays.fetch_add(1, Ordering::Relaxed);
+                [0.0, 0.0, 0.0]
+            }
+        },
+    }
+}
+
+/// Stand-in single-bounce shading: how directly `normal` faces a fixed
+/// key light, rather than a full recursive path trace.
+fn facing_light(normal: [f64; 3]) -> f64 {
+    let light_dir = normalize([0.4, 0.8, 0.6]);
+    dot(normal, light_dir).max(0.0)
+}
+
+fn background(dir: [f64; 3]) -> [f64; 3] {
+    let unit = normalize(dir);
+    let t = 0.5 * (unit[1] + 1.0);
+    add(scale([1.0, 1.0, 1.0], 1.0 - t), scale([0.5, 0.7, 1.0], t))
+}
+
+fn to_rgb8(color: [f64; 3]) -> [u8; 3] {
+    let channel = |c: f64| (c.max(0.0).sqrt().min(1.0) * 255.0) as u8;
+    [channel(color[0]), channel(color[1]), channel(color[2])]
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(a: [f64; 3], t: f64) -> [f64; 3] {
+    [a[0] * t, a[1] * t, a[2] * t]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn normalize(a: [f64; 3]) -> [f64; 3] {
+    let len = (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt();
+    [a[0] / len, a[1] / len, a[2] / len]
+}
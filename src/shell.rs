@@ -0,0 +1,239 @@
+//! Central home for all user-facing output.
+//!
+//! Before this module existed, `main.rs` wrote directly to stdout/stderr via
+//! a mix of `println!`, `eprintln!`, `StepLogger` and a standalone
+//! `indicatif` progress bar, which made it impossible to offer a machine
+//! readable mode without threading ad-hoc flags through every call site.
+//! `Shell` collects all of that in one place and dispatches on a `ShellMode`
+//! selected once at startup from the global `--quiet`/`--json` flags:
+//!
+//! - `Normal`: the existing colored step/progress-bar output.
+//! - `Quiet`: steps and the progress bar are suppressed; only errors print.
+//! - `Json`: every event (step transitions, render statistics, errors) is
+//!   emitted as a line of newline-delimited JSON on stdout instead of human
+//!   text, so the tool can be driven from scripts and CI.
+
+use console::style;
+use indicatif::{FormattedDuration, ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+const PROGRESS_BAR_STYLE: &str = "[{elapsed_precise}] [{bar:60.cyan/blue}] {percent}% ({eta})";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShellMode {
+    Normal,
+    Quiet,
+    Json,
+}
+
+/// Owns all user-facing output for a single run of `rayt`.
+pub struct Shell {
+    mode: ShellMode,
+    step: u8,
+    num_of_steps: u8,
+    current_step: Option<(String, Instant)>,
+    step_durations: Vec<(String, Duration)>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum ShellEvent<'a> {
+    Step {
+        index: u8,
+        total: u8,
+        message: &'a str,
+    },
+    StepFinished {
+        message: &'a str,
+        elapsed_ms: u128,
+    },
+    TimingBreakdown {
+        steps: Vec<TimingBreakdownStep<'a>>,
+    },
+    RenderStats {
+        failed_rays: u64,
+        width: u32,
+        height: u32,
+        output_path: &'a str,
+        elapsed_ms: u128,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// One row of the `TimingBreakdown` event, matching the flat
+/// `{message, elapsed_ms}` shape `StepFinished` and `RenderStats` already
+/// use rather than `Duration`'s own `{secs, nanos}` serialization.
+#[derive(Serialize)]
+struct TimingBreakdownStep<'a> {
+    message: &'a str,
+    elapsed_ms: u128,
+}
+
+impl Shell {
+    pub fn new(mode: ShellMode) -> Shell {
+        Shell {
+            mode,
+            step: 1,
+            num_of_steps: 0,
+            current_step: None,
+            step_durations: Vec::new(),
+        }
+    }
+
+    pub fn mode(&self) -> ShellMode {
+        self.mode
+    }
+
+    /// Begin a sequence of `num_of_steps` steps, resetting the step counter
+    /// and any timing recorded by a previous sequence.
+    pub fn begin_steps(&mut self, num_of_steps: u8) {
+        self.step = 1;
+        self.num_of_steps = num_of_steps;
+        self.current_step = None;
+        self.step_durations = Vec::with_capacity(usize::from(num_of_steps));
+    }
+
+    /// Announce the start of the next step, printing (or emitting, in JSON
+    /// mode) `[n/m] msg...`. If a previous step is in flight, its elapsed
+    /// time is reported first.
+    pub fn step(&mut self, msg: &str) {
+        assert!(self.step <= self.num_of_steps);
+
+        self.finish_current_step();
+
+        match self.mode {
+            ShellMode::Normal => println!(
+                "{}{}{}{}{} {}...",
+                style("[").bold().dim(),
+                style(self.step.to_string()).bold().dim(),
+                style("/").bold().dim(),
+                style(self.num_of_steps.to_string()).bold().dim(),
+                style("]").bold().dim(),
+                msg,
+            ),
+            ShellMode::Quiet => {}
+            ShellMode::Json => self.emit(&ShellEvent::Step {
+                index: self.step,
+                total: self.num_of_steps,
+                message: msg,
+            }),
+        }
+
+        self.current_step = Some((msg.to_string(), Instant::now()));
+        self.step += 1;
+    }
+
+    /// Close out the last step and print (or emit) a breakdown of how long
+    /// each step in the sequence took.
+    pub fn finish(&mut self) {
+        self.finish_current_step();
+
+        match self.mode {
+            ShellMode::Normal => {
+                println!("{}", style("timing breakdown:").bold().dim());
+                for (message, duration) in &self.step_durations {
+                    println!("  {:<40} {}", message, FormattedDuration(*duration));
+                }
+            }
+            ShellMode::Quiet => {}
+            ShellMode::Json => self.emit(&ShellEvent::TimingBreakdown {
+                steps: self
+                    .step_durations
+                    .iter()
+                    .map(|(message, duration)| TimingBreakdownStep {
+                        message,
+                        elapsed_ms: duration.as_millis(),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn finish_current_step(&mut self) {
+        if let Some((message, started)) = self.current_step.take() {
+            let elapsed = started.elapsed();
+
+            match self.mode {
+                ShellMode::Normal => println!("  done in {}", FormattedDuration(elapsed)),
+                ShellMode::Quiet => {}
+                ShellMode::Json => self.emit(&ShellEvent::StepFinished {
+                    message: &message,
+                    elapsed_ms: elapsed.as_millis(),
+                }),
+            }
+
+            self.step_durations.push((message, elapsed));
+        }
+    }
+
+    /// Report final render statistics. Called once after a render completes.
+    pub fn render_stats(&self, failed_rays: u64, width: u32, height: u32, output_path: &str, elapsed: Duration) {
+        match self.mode {
+            ShellMode::Normal => {
+                if failed_rays > 0 {
+                    println!(
+                        "{} {} rays failed while rendering",
+                        style("warning:").yellow(),
+                        failed_rays,
+                    );
+                }
+                println!(
+                    "Wrote {}x{} image to {}",
+                    width, height, output_path,
+                );
+                println!(
+                    "Done in {}",
+                    indicatif::FormattedDuration(elapsed),
+                );
+            }
+            ShellMode::Quiet => {}
+            ShellMode::Json => self.emit(&ShellEvent::RenderStats {
+                failed_rays,
+                width,
+                height,
+                output_path,
+                elapsed_ms: elapsed.as_millis(),
+            }),
+        }
+    }
+
+    /// Report a fatal error. Always prints/emits, even in `--quiet` mode.
+    pub fn error(&self, err: &anyhow::Error) {
+        match self.mode {
+            ShellMode::Normal | ShellMode::Quiet => {
+                eprintln!("{} {}", style("error:").red(), err)
+            }
+            ShellMode::Json => self.emit(&ShellEvent::Error {
+                message: err.to_string(),
+            }),
+        }
+    }
+
+    /// Build a progress bar for the render loop, or `None` when the bar
+    /// would be suppressed (`--quiet`/`--json`).
+    pub fn progress_bar(&self, bar_size: u64) -> Option<ProgressBar> {
+        match self.mode {
+            ShellMode::Normal => {
+                let progress_style = ProgressStyle::default_bar()
+                    .template(PROGRESS_BAR_STYLE)
+                    .progress_chars("##-");
+                let progress_bar = ProgressBar::new(bar_size);
+                progress_bar.set_style(progress_style);
+                progress_bar.tick();
+                progress_bar.set_draw_delta(bar_size / 1000);
+                Some(progress_bar)
+            }
+            ShellMode::Quiet | ShellMode::Json => None,
+        }
+    }
+
+    fn emit(&self, event: &ShellEvent) {
+        match serde_json::to_string(event) {
+            Ok(line) => println!("{}", line),
+            Err(e) => eprintln!("{} failed to serialize shell event: {}", style("error:").red(), e),
+        }
+    }
+}
@@ -0,0 +1,192 @@
+//! The scene's renderable geometry: native spheres plus any objects whose
+//! intersection test is owned by a plugin.
+//!
+//! `World::build` resolves every object's bounding box up front: native
+//! spheres compute their own, and plugin objects are asked for theirs once
+//! via `Plugin::bounding_box` (the answer is cached for the life of the
+//! render by `Plugin` itself). There's no tree over these boxes — `hit_spheres`
+//! scans every object, and the renderer calls `bounding_box_hit` once per
+//! plugin object — but the renderer
+//! still uses each box to skip the real intersection test (native or plugin)
+//! for rays that can't possibly hit it.
+
+use crate::plugin::PluginManager;
+use anyhow::{anyhow, Error};
+
+#[derive(Debug, Clone, Copy)]
+struct Aabb {
+    min: [f64; 3],
+    max: [f64; 3],
+}
+
+impl Aabb {
+    fn hit(&self, origin: [f64; 3], dir: [f64; 3], mut t_min: f64, mut t_max: f64) -> bool {
+        for axis in 0..3 {
+            let inv_d = 1.0 / dir[axis];
+            let mut t0 = (self.min[axis] - origin[axis]) * inv_d;
+            let mut t1 = (self.max[axis] - origin[axis]) * inv_d;
+            if inv_d < 0.0 {
+                std::mem::swap(&mut t0, &mut t1);
+            }
+            t_min = t_min.max(t0);
+            t_max = t_max.min(t1);
+            if t_max <= t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// A resolved scene object: either a primitive rayt understands natively,
+/// or one whose geometry is owned by the plugin that claimed
+/// `plugin_type`, identified to that plugin by `id` (the object's id in
+/// the scene config).
+#[derive(Debug, Clone)]
+pub enum Object {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: String,
+    },
+    Plugin {
+        id: String,
+        plugin_type: String,
+        material: String,
+    },
+}
+
+impl Object {
+    pub fn material(&self) -> &str {
+        match self {
+            Object::Sphere { material, .. } => material,
+            Object::Plugin { material, .. } => material,
+        }
+    }
+}
+
+/// A material resolved at config-load time: native `Lambertian`, or
+/// `Plugin`, whose `type_name` is the material type a plugin claimed and
+/// whose `id` is the material's name in the scene config.
+#[derive(Debug, Clone)]
+pub enum Material {
+    Lambertian { albedo: [f64; 3] },
+    Plugin { type_name: String, id: String },
+}
+
+/// One ray/object intersection, in world space.
+#[derive(Debug, Clone)]
+pub struct HitRecord {
+    pub t: f64,
+    pub point: [f64; 3],
+    pub normal: [f64; 3],
+    pub material: String,
+    pub u: f64,
+    pub v: f64,
+}
+
+pub struct World {
+    objects: Vec<Object>,
+    bounds: Vec<Aabb>,
+}
+
+impl World {
+    pub fn build(objects: Vec<Object>, plugins: &PluginManager) -> Result<World, Error> {
+        let bounds = objects
+            .iter()
+            .map(|object| match object {
+                Object::Sphere { center, radius, .. } => Ok(Aabb {
+                    min: [center[0] - radius, center[1] - radius, center[2] - radius],
+                    max: [center[0] + radius, center[1] + radius, center[2] + radius],
+                }),
+                Object::Plugin { id, plugin_type, .. } => {
+                    let plugin = plugins
+                        .resolve_object(plugin_type)
+                        .ok_or_else(|| anyhow!("no plugin claims object type <{}>", plugin_type))?;
+                    let bbox = plugin.bounding_box(id)?;
+                    Ok(Aabb { min: bbox.min, max: bbox.max })
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        Ok(World { objects, bounds })
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    /// Whether the ray can possibly hit the object at `index`'s cached
+    /// bounding box. Used by the renderer to skip plugin dispatch for rays
+    /// that miss a plugin object entirely, the same way `hit_spheres`
+    /// already skips the real intersection test for native spheres.
+    pub fn bounding_box_hit(&self, index: usize, origin: [f64; 3], dir: [f64; 3], t_min: f64, t_max: f64) -> bool {
+        self.bounds[index].hit(origin, dir, t_min, t_max)
+    }
+
+    /// Native sphere intersection, skipping any object whose bounding box
+    /// the ray misses. Plugin objects are intersected separately, in one
+    /// batch per render tile (see `renderer::render_row`), so they're
+    /// skipped here.
+    pub fn hit_spheres(&self, origin: [f64; 3], dir: [f64; 3], t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut record = None;
+
+        for (object, bounds) in self.objects.iter().zip(&self.bounds) {
+            let (center, radius, material) = match object {
+                Object::Sphere { center, radius, material } => (*center, *radius, material),
+                Object::Plugin { .. } => continue,
+            };
+            if !bounds.hit(origin, dir, t_min, closest) {
+                continue;
+            }
+
+            let oc = sub(origin, center);
+            let a = dot(dir, dir);
+            let half_b = dot(oc, dir);
+            let c = dot(oc, oc) - radius * radius;
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                continue;
+            }
+            let sqrt_d = discriminant.sqrt();
+            let mut root = (-half_b - sqrt_d) / a;
+            if root < t_min || root > closest {
+                root = (-half_b + sqrt_d) / a;
+                if root < t_min || root > closest {
+                    continue;
+                }
+            }
+
+            let point = add(origin, scale(dir, root));
+            let normal = scale(sub(point, center), 1.0 / radius);
+            closest = root;
+            record = Some(HitRecord {
+                t: root,
+                point,
+                normal,
+                material: material.clone(),
+                u: 0.0,
+                v: 0.0,
+            });
+        }
+
+        record
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn add(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn scale(a: [f64; 3], t: f64) -> [f64; 3] {
+    [a[0] * t, a[1] * t, a[2] * t]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}